@@ -1,14 +1,39 @@
 use hex;
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
 use sha2::{Digest, Sha256};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
+use crate::encoding::{
+    decode_compact_size, decode_hash, decode_var_bytes, encode_compact_size, encode_hash,
+    encode_var_bytes, read_bytes, Decodable, Encodable,
+};
 use crate::linked_list::LinkedList as List;
 use crate::mresult::MResult as Result;
+use crate::pow::U256;
+use crate::utxo::UtxoSet;
+use crate::wallet;
+
+/// How many blocks pass between difficulty retargets.
+const RETARGET_INTERVAL: u64 = 10;
+/// The desired number of seconds between blocks, Bitcoin-style.
+const BLOCK_SPACING_SECONDS: u64 = 600;
+/// The easiest target representable in compact bits (the same ceiling
+/// Bitcoin's regtest network uses), used for unmined blocks and as the
+/// retargeting ceiling.
+const MAX_TARGET_BITS: u32 = 0x207fffff;
 
 pub struct BlockChain {
-    blocks: List<Block>,
     block_index: HashMap<String, Block>,
+    /// Every block's children, keyed by the parent's hash, so competing
+    /// branches off the same block can coexist.
+    children: HashMap<String, Vec<String>>,
+    /// The cumulative proof-of-work, from genesis, behind each known block.
+    cumulative_work: HashMap<String, U256>,
+    /// The hash of the tip of the active (greatest cumulative work) chain.
+    active_tip: Option<String>,
+    /// Maps height to hash along the active chain only.
     height_index: HashMap<u128, String>,
+    utxo_set: UtxoSet,
 }
 
 #[derive(Clone)]
@@ -20,6 +45,9 @@ pub struct Block {
     timestamp: u64,
     merkle_root: String,
     nonce: u32,
+    /// The block's difficulty target in Bitcoin's compact "nBits" encoding,
+    /// or `None` if the block has not been mined.
+    bits: Option<u32>,
 }
 
 #[derive(Clone)]
@@ -46,34 +74,292 @@ pub struct TxOut {
 impl BlockChain {
     pub fn new() -> Self {
         BlockChain {
-            blocks: List::new(),
             block_index: HashMap::new(),
+            children: HashMap::new(),
+            cumulative_work: HashMap::new(),
+            active_tip: None,
             height_index: HashMap::new(),
+            utxo_set: UtxoSet::new(),
         }
     }
 
+    /// Adds a block to the tree of known blocks. If the block's branch now
+    /// carries more cumulative work than the active chain, the active chain
+    /// is reorganized onto it.
     pub fn add_block(&mut self, block: Block) -> Result<(), &'static str> {
+        if self.block_index.contains_key(&block.hash) {
+            return Result::Err("block already known");
+        }
         if !self.is_valid_block(&block) {
             return Result::Err("Invalid block");
         }
 
+        let block_work = block
+            .target()
+            .unwrap_or_else(|| U256::from_compact(MAX_TARGET_BITS))
+            .work();
+        let branch_work = if block.height == 0 {
+            block_work
+        } else {
+            match self.cumulative_work.get(&block.prev_block_hash) {
+                Some(parent_work) => parent_work.add(&block_work),
+                None => return Result::Err("unknown previous block"),
+            }
+        };
+
         let block_hash = block.hash.clone();
-        let block_height = block.height;
+        self.cumulative_work.insert(block_hash.clone(), branch_work);
+        self.children
+            .entry(block.prev_block_hash.clone())
+            .or_default()
+            .push(block_hash.clone());
+        self.block_index.insert(block_hash.clone(), block);
 
-        self.height_index
-            .insert(block_height.into(), block_hash.clone());
-        self.blocks.push_front(block.clone());
-        self.block_index.insert(block_hash, block);
+        let is_new_best = match self
+            .active_tip
+            .as_ref()
+            .and_then(|tip| self.cumulative_work.get(tip))
+        {
+            Some(&active_work) => branch_work > active_work,
+            None => true,
+        };
 
-        Result::Ok(())
+        if is_new_best {
+            self.reorganize(&block_hash)
+        } else {
+            Result::Ok(())
+        }
     }
 
+    /// Structural and proof-of-work checks that hold regardless of which
+    /// branch a block belongs to. Transaction validity against the UTXO set
+    /// is checked separately, only for the branch that becomes active (see
+    /// `reorganize`), since that's the only state a non-active branch's
+    /// blocks can be validated against.
     pub fn is_valid_block(&self, block: &Block) -> bool {
-        if block.height > 0 {
-            self.get_block_by_hash(&block.prev_block_hash).is_some()
-        } else {
-            true // Genesis block
+        if block.height > 0 && self.get_block_by_hash(&block.prev_block_hash).is_none() {
+            return false;
+        }
+
+        if let Some(target) = block.target() {
+            if U256::from_be_hex(&block.hash) > target {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Switches the active chain to the branch ending at `new_tip_hash`:
+    /// undoes the UTXO changes of any abandoned blocks back to the common
+    /// ancestor, then re-applies the new branch's blocks from there,
+    /// validating transactions as it goes. If a block along the new branch
+    /// fails validation, the active chain is left unchanged.
+    pub fn reorganize(&mut self, new_tip_hash: &str) -> Result<(), &'static str> {
+        if !self.block_index.contains_key(new_tip_hash) {
+            return Result::Err("unknown block");
+        }
+
+        let new_chain = self.chain_to_genesis(new_tip_hash);
+        let old_chain = match &self.active_tip {
+            Some(tip) => self.chain_to_genesis(tip),
+            None => Vec::new(),
+        };
+
+        let fork_index = old_chain
+            .iter()
+            .zip(new_chain.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        for hash in old_chain[fork_index..].iter().rev() {
+            let block = self.block_index.get(hash).unwrap().clone();
+            self.undo_block(&block);
+        }
+
+        let mut applied = Vec::new();
+        for hash in &new_chain[fork_index..] {
+            let block = self.block_index.get(hash).unwrap().clone();
+            if !self.validate_transactions(&block) {
+                for applied_hash in applied.iter().rev() {
+                    let applied_block: Block = self.block_index.get(applied_hash).unwrap().clone();
+                    self.undo_block(&applied_block);
+                }
+                for hash in &old_chain[fork_index..] {
+                    let block = self.block_index.get(hash).unwrap().clone();
+                    self.apply_block(&block);
+                }
+                return Result::Err("Invalid block");
+            }
+            self.apply_block(&block);
+            applied.push(hash.clone());
+        }
+
+        let new_tip_height = self.block_index.get(new_tip_hash).unwrap().height;
+        self.height_index.retain(|height, _| *height <= new_tip_height.into());
+        for hash in &new_chain {
+            let height = self.block_index.get(hash).unwrap().height;
+            self.height_index.insert(height.into(), hash.clone());
+        }
+        self.active_tip = Some(new_tip_hash.to_string());
+
+        Result::Ok(())
+    }
+
+    /// Walks a block's ancestry back to genesis and returns the chain of
+    /// hashes in genesis-to-tip order.
+    fn chain_to_genesis(&self, tip_hash: &str) -> Vec<String> {
+        let mut chain = Vec::new();
+        let mut hash = tip_hash.to_string();
+        while let Some(block) = self.block_index.get(&hash) {
+            chain.push(hash.clone());
+            if block.height == 0 {
+                break;
+            }
+            hash = block.prev_block_hash.clone();
         }
+        chain.reverse();
+        chain
+    }
+
+    /// The target the next block should be mined against: unchanged from
+    /// the active tip except every `RETARGET_INTERVAL` blocks, when it is
+    /// recomputed from the ratio of actual to desired time elapsed over
+    /// the period, clamped to a 4x adjustment in either direction.
+    pub fn next_target(&self) -> U256 {
+        let max_target = U256::from_compact(MAX_TARGET_BITS);
+
+        let tip = match self.active_tip.as_ref().and_then(|hash| self.get_block_by_hash(hash)) {
+            Some(tip) => tip,
+            None => return max_target,
+        };
+        let tip_target = tip.target().unwrap_or(max_target);
+
+        let next_height = tip.height + 1;
+        if next_height % RETARGET_INTERVAL != 0 {
+            return tip_target;
+        }
+
+        let period_start_height = next_height - RETARGET_INTERVAL;
+        let period_start = match self.get_block_by_height(period_start_height.into()) {
+            Some(block) => block,
+            None => return tip_target,
+        };
+
+        let desired_timespan = RETARGET_INTERVAL * BLOCK_SPACING_SECONDS;
+        let actual_timespan = tip.timestamp.saturating_sub(period_start.timestamp);
+        let clamped_timespan =
+            actual_timespan.clamp(desired_timespan / 4, desired_timespan * 4);
+
+        let new_target = tip_target
+            .saturating_mul_u64(clamped_timespan)
+            .div_u64(desired_timespan);
+
+        std::cmp::min(new_target, max_target)
+    }
+
+    /// Checks every non-coinbase transaction in `block` against the current
+    /// UTXO set: inputs must reference outpoints that exist, outpoints must
+    /// not be double-spent within the block, and inputs must cover outputs.
+    /// Outputs created earlier in the same block are visible to later
+    /// transactions in that block.
+    fn validate_transactions(&self, block: &Block) -> bool {
+        let mut spent_in_block: HashSet<(String, usize)> = HashSet::new();
+        let mut created_in_block: HashMap<(String, usize), &TxOut> = HashMap::new();
+
+        for tx in block.transactions() {
+            if !tx.is_coinbase() {
+                let mut input_total: u64 = 0;
+
+                for (index, input) in tx.inputs().enumerate() {
+                    let outpoint = (input.prev_txid().to_string(), input.vout());
+                    if spent_in_block.contains(&outpoint) {
+                        return false;
+                    }
+
+                    let txout = match created_in_block.get(&outpoint) {
+                        Some(txout) => *txout,
+                        None => match self.utxo_set.get_utxo(&outpoint.0, outpoint.1) {
+                            Some(txout) => txout,
+                            None => return false,
+                        },
+                    };
+
+                    if !tx.verify_input(index, txout) {
+                        return false;
+                    }
+
+                    input_total += txout.satoshis();
+                    spent_in_block.insert(outpoint);
+                }
+
+                let output_total: u64 = tx.outputs().map(|txout| txout.satoshis()).sum();
+                if input_total < output_total {
+                    return false;
+                }
+            }
+
+            for (vout, txout) in tx.outputs().enumerate() {
+                created_in_block.insert((tx.txid().to_string(), vout), txout);
+            }
+        }
+
+        true
+    }
+
+    /// Applies a validated block's transactions to the UTXO set: consumed
+    /// outpoints are removed and new outputs are inserted.
+    fn apply_block(&mut self, block: &Block) {
+        for tx in block.transactions() {
+            if !tx.is_coinbase() {
+                for input in tx.inputs() {
+                    self.utxo_set.remove(input.prev_txid(), input.vout());
+                }
+            }
+
+            for (vout, txout) in tx.outputs().enumerate() {
+                self.utxo_set
+                    .insert(tx.txid().to_string(), vout, txout.clone());
+            }
+        }
+    }
+
+    /// Reverses `apply_block`: the outputs it created are removed from the
+    /// UTXO set, and the outputs its inputs spent are restored by looking
+    /// up the transaction that originally created them.
+    fn undo_block(&mut self, block: &Block) {
+        for tx in block.transactions() {
+            for vout in 0..tx.outputs().count() {
+                self.utxo_set.remove(tx.txid(), vout);
+            }
+
+            if !tx.is_coinbase() {
+                for input in tx.inputs() {
+                    if let Some(txout) = self.find_output(input.prev_txid(), input.vout()) {
+                        self.utxo_set
+                            .insert(input.prev_txid().to_string(), input.vout(), txout);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Finds the output a now-spent outpoint originally pointed to, by
+    /// searching every known block for the transaction that created it.
+    fn find_output(&self, txid: &str, vout: usize) -> Option<TxOut> {
+        self.block_index.values().find_map(|block| {
+            block
+                .get_transaction(txid)
+                .and_then(|tx| tx.outputs().nth(vout).cloned())
+        })
+    }
+
+    pub fn get_utxo(&self, txid: &str, vout: usize) -> Option<&TxOut> {
+        self.utxo_set.get_utxo(txid, vout)
+    }
+
+    pub fn get_balance(&self, public_address: &str) -> u64 {
+        self.utxo_set.get_balance(public_address)
     }
 
     pub fn get_block_by_hash(&self, hash: &str) -> Option<&Block> {
@@ -86,57 +372,208 @@ impl BlockChain {
             .and_then(|hash| self.block_index.get(hash))
     }
 
+    /// The number of blocks on the active chain.
     pub fn get_block_count(&self) -> usize {
-        self.blocks.iter().count()
+        self.height_index.len()
     }
 
+    /// Searches the active chain (not side branches) for a transaction.
     pub fn get_transaction(&self, txid: &str) -> Option<&Transaction> {
-        self.blocks
-            .iter()
-            .find_map(|block| block.get_transaction(txid))
+        let mut hash = self.active_tip.clone()?;
+        loop {
+            let block = self.block_index.get(&hash)?;
+            if let Some(tx) = block.get_transaction(txid) {
+                return Some(tx);
+            }
+            if block.height == 0 {
+                return None;
+            }
+            hash = block.prev_block_hash.clone();
+        }
     }
 
     pub fn get_best_block_hash(&self) -> Option<String> {
-        self.blocks
-            .iter()
-            .next()
-            .map(|block| block.hash.to_string())
+        self.active_tip.clone()
+    }
+}
+
+impl Default for BlockChain {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Block {
+    /// Builds a new block header. Fails if `prev_block_hash` isn't a valid
+    /// 32-byte hex hash, since the header can't be serialized (and thus
+    /// hashed) otherwise.
     pub fn new(
         height: u64,
         prev_block_hash: String,
         timestamp: u64,
-        merkle_root: String,
         nonce: u32,
-    ) -> Self {
+    ) -> Result<Self, &'static str> {
+        if let Result::Err(err) = encode_hash(&prev_block_hash, &mut Vec::new()) {
+            return Result::Err(err);
+        }
+
         let mut block = Block {
             hash: String::new(),
             height,
             transactions: List::new(),
             prev_block_hash,
             timestamp,
-            merkle_root,
+            merkle_root: String::new(),
             nonce,
+            // A block only carries a proof-of-work target once it has been
+            // mined; unmined blocks are exempt from the PoW check.
+            bits: None,
         };
+        block.merkle_root = block.compute_merkle_root();
         block.hash = block.calculate_hash();
-        block
+        Result::Ok(block)
+    }
+
+    /// Increments `nonce` until `calculate_hash()` satisfies `target`,
+    /// Bitcoin's proof-of-work puzzle. The target is stored back onto the
+    /// block in its canonical compact-bits form, and mining solves against
+    /// that canonical (possibly rounded) value.
+    pub fn mine(&mut self, target: U256) {
+        let bits = target.to_compact();
+        self.bits = Some(bits);
+        let canonical_target = U256::from_compact(bits);
+
+        loop {
+            self.hash = self.calculate_hash();
+            if U256::from_be_hex(&self.hash) <= canonical_target {
+                break;
+            }
+            self.nonce = self.nonce.wrapping_add(1);
+        }
+    }
+
+    pub fn bits(&self) -> Option<u32> {
+        self.bits
+    }
+
+    /// The target this block was mined against, if it was mined at all.
+    pub fn target(&self) -> Option<U256> {
+        self.bits.map(U256::from_compact)
+    }
+
+    /// Builds the Bitcoin-style Merkle tree over this block's transaction
+    /// ids and returns the hex-encoded root. An empty block has a
+    /// well-defined all-zero root; a single-transaction block's root is
+    /// that transaction's hash.
+    pub fn compute_merkle_root(&self) -> String {
+        let mut layer: Vec<[u8; 32]> = self
+            .transactions
+            .iter()
+            .map(|tx| {
+                let bytes = hex::decode(&tx.txid).expect("txid is valid hex");
+                let mut node = [0u8; 32];
+                node.copy_from_slice(&bytes);
+                node
+            })
+            .collect();
+
+        if layer.is_empty() {
+            return hex::encode([0u8; 32]);
+        }
+
+        while layer.len() > 1 {
+            if layer.len() % 2 == 1 {
+                layer.push(*layer.last().unwrap());
+            }
+
+            layer = layer
+                .chunks(2)
+                .map(|pair| {
+                    let mut concat = Vec::with_capacity(64);
+                    concat.extend_from_slice(&pair[0]);
+                    concat.extend_from_slice(&pair[1]);
+                    dhash256(&concat)
+                })
+                .collect();
+        }
+
+        hex::encode(layer[0])
+    }
+
+    /// Builds an SPV-style inclusion proof for `txid`: the ordered list of
+    /// sibling hashes along the path from its leaf to the Merkle root, each
+    /// tagged with which side of the pair the sibling falls on. Returns
+    /// `None` if no transaction in this block has that txid. Follows the
+    /// same odd-node duplication rule as `compute_merkle_root`.
+    pub fn merkle_proof(&self, txid: &str) -> Option<MerkleProof> {
+        let mut layer: Vec<[u8; 32]> = self
+            .transactions
+            .iter()
+            .map(|tx| {
+                let bytes = hex::decode(&tx.txid).expect("txid is valid hex");
+                let mut node = [0u8; 32];
+                node.copy_from_slice(&bytes);
+                node
+            })
+            .collect();
+
+        let target = decode_txid(txid)?;
+        let mut index = layer.iter().position(|node| *node == target)?;
+
+        let mut steps = Vec::new();
+
+        while layer.len() > 1 {
+            if layer.len() % 2 == 1 {
+                layer.push(*layer.last().unwrap());
+            }
+
+            let (sibling_index, side) = if index % 2 == 0 {
+                (index + 1, MerkleSide::Right)
+            } else {
+                (index - 1, MerkleSide::Left)
+            };
+            steps.push(MerkleProofStep {
+                sibling: layer[sibling_index],
+                side,
+            });
+
+            layer = layer
+                .chunks(2)
+                .map(|pair| {
+                    let mut concat = Vec::with_capacity(64);
+                    concat.extend_from_slice(&pair[0]);
+                    concat.extend_from_slice(&pair[1]);
+                    dhash256(&concat)
+                })
+                .collect();
+            index /= 2;
+        }
+
+        Some(MerkleProof { steps })
     }
 
+    /// Double-SHA256 of the serialized 88-byte header (height, hashes,
+    /// timestamp, bits and nonce) — the same scope Bitcoin hashes, so
+    /// transactions only affect the hash through the Merkle root.
     pub fn calculate_hash(&self) -> String {
-        let mut hasher = Sha256::new();
-        hasher.update(self.height.to_string());
-        hasher.update(&self.prev_block_hash);
-        hasher.update(self.timestamp.to_string());
-        hasher.update(&self.merkle_root);
-        hasher.update(self.nonce.to_string());
-        hex::encode(hasher.finalize())
+        let mut buf = Vec::new();
+        self.encode_header(&mut buf);
+        hex::encode(dhash256(&buf))
+    }
+
+    fn encode_header(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.height.to_le_bytes());
+        encode_hash(&self.prev_block_hash, buf).unwrap();
+        buf.extend_from_slice(&self.timestamp.to_le_bytes());
+        let merkle_root = self.compute_merkle_root();
+        encode_hash(&merkle_root, buf).unwrap();
+        buf.extend_from_slice(&self.nonce.to_le_bytes());
+        buf.extend_from_slice(&self.bits.unwrap_or(0).to_le_bytes());
     }
 
     pub fn add_transaction(&mut self, transaction: Transaction) -> Result<(), &'static str> {
         self.transactions.push_front(transaction);
+        self.merkle_root = self.compute_merkle_root();
         self.hash = self.calculate_hash();
 
         Result::Ok(())
@@ -145,32 +582,370 @@ impl Block {
     pub fn get_transaction(&self, txid: &str) -> Option<&Transaction> {
         self.transactions.iter().find(|tx| tx.txid == txid)
     }
+
+    pub fn transactions(&self) -> impl Iterator<Item = &Transaction> {
+        self.transactions.iter()
+    }
+}
+
+impl Encodable for Block {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.encode_header(buf);
+
+        let transactions: Vec<&Transaction> = self.transactions.iter().collect();
+        encode_compact_size(transactions.len() as u64, buf);
+        for transaction in &transactions {
+            transaction.encode(buf);
+        }
+    }
+}
+
+impl Decodable for Block {
+    fn decode(buf: &[u8], cursor: &mut usize) -> Result<Self, &'static str> {
+        let height = match read_bytes(buf, cursor, 8) {
+            Result::Ok(bytes) => u64::from_le_bytes(bytes.try_into().unwrap()),
+            Result::Err(err) => return Result::Err(err),
+        };
+        let prev_block_hash = match decode_hash(buf, cursor) {
+            Result::Ok(hash) => hash,
+            Result::Err(err) => return Result::Err(err),
+        };
+        let timestamp = match read_bytes(buf, cursor, 8) {
+            Result::Ok(bytes) => u64::from_le_bytes(bytes.try_into().unwrap()),
+            Result::Err(err) => return Result::Err(err),
+        };
+        let merkle_root = match decode_hash(buf, cursor) {
+            Result::Ok(hash) => hash,
+            Result::Err(err) => return Result::Err(err),
+        };
+        let nonce = match read_bytes(buf, cursor, 4) {
+            Result::Ok(bytes) => u32::from_le_bytes(bytes.try_into().unwrap()),
+            Result::Err(err) => return Result::Err(err),
+        };
+        let bits_raw = match read_bytes(buf, cursor, 4) {
+            Result::Ok(bytes) => u32::from_le_bytes(bytes.try_into().unwrap()),
+            Result::Err(err) => return Result::Err(err),
+        };
+        let transaction_count = match decode_compact_size(buf, cursor) {
+            Result::Ok(count) => count,
+            Result::Err(err) => return Result::Err(err),
+        };
+
+        let mut decoded_transactions = Vec::with_capacity(transaction_count as usize);
+        for _ in 0..transaction_count {
+            match Transaction::decode(buf, cursor) {
+                Result::Ok(transaction) => decoded_transactions.push(transaction),
+                Result::Err(err) => return Result::Err(err),
+            }
+        }
+        let mut transactions = List::new();
+        for transaction in decoded_transactions.into_iter().rev() {
+            transactions.push_front(transaction);
+        }
+
+        let mut block = Block {
+            hash: String::new(),
+            height,
+            transactions,
+            prev_block_hash,
+            timestamp,
+            merkle_root,
+            nonce,
+            bits: if bits_raw == 0 { None } else { Some(bits_raw) },
+        };
+        block.hash = block.calculate_hash();
+
+        Result::Ok(block)
+    }
+}
+
+/// SHA256(SHA256(x)), the double hash Bitcoin uses throughout consensus code.
+fn dhash256(data: &[u8]) -> [u8; 32] {
+    let first = Sha256::digest(data);
+    let second = Sha256::digest(first);
+    second.into()
+}
+
+fn decode_txid(txid: &str) -> Option<[u8; 32]> {
+    let bytes = hex::decode(txid).ok()?;
+    if bytes.len() != 32 {
+        return None;
+    }
+    let mut node = [0u8; 32];
+    node.copy_from_slice(&bytes);
+    Some(node)
+}
+
+/// Which side of a pair a proof step's sibling hash falls on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MerkleSide {
+    Left,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MerkleProofStep {
+    pub sibling: [u8; 32],
+    pub side: MerkleSide,
+}
+
+/// An SPV-style inclusion proof: the sibling hashes along the path from a
+/// transaction's leaf up to its block's Merkle root, produced by
+/// `Block::merkle_proof`.
+pub struct MerkleProof {
+    steps: Vec<MerkleProofStep>,
+}
+
+impl MerkleProof {
+    /// Recomputes the Merkle root by folding `dhash256` up the proof's path
+    /// from `txid`'s leaf, and checks it against `expected_root`.
+    pub fn verify(&self, txid: &str, expected_root: &str) -> bool {
+        let mut current = match decode_txid(txid) {
+            Some(node) => node,
+            None => return false,
+        };
+
+        for step in &self.steps {
+            let mut concat = Vec::with_capacity(64);
+            match step.side {
+                MerkleSide::Right => {
+                    concat.extend_from_slice(&current);
+                    concat.extend_from_slice(&step.sibling);
+                }
+                MerkleSide::Left => {
+                    concat.extend_from_slice(&step.sibling);
+                    concat.extend_from_slice(&current);
+                }
+            }
+            current = dhash256(&concat);
+        }
+
+        hex::encode(current) == expected_root
+    }
 }
 
 impl Transaction {
-    pub fn new(inputs: List<TxIn>, outputs: List<TxOut>) -> Self {
+    /// Builds a new transaction. Fails if any input's `prev_txid` isn't a
+    /// valid 32-byte hex hash, since the transaction can't be serialized
+    /// (and thus given a txid) otherwise.
+    pub fn new(inputs: List<TxIn>, outputs: List<TxOut>) -> Result<Self, &'static str> {
+        for input in inputs.iter() {
+            if let Result::Err(err) = encode_hash(&input.prev_txid, &mut Vec::new()) {
+                return Result::Err(err);
+            }
+        }
+
         let mut tx = Transaction {
             inputs,
             outputs,
             txid: String::new(),
         };
         tx.txid = tx.calculate_txid();
-        tx
+        Result::Ok(tx)
     }
 
+    /// Double-SHA256 of the transaction's serialized consensus encoding.
     pub fn calculate_txid(&self) -> String {
-        let mut hasher = Sha256::new();
-        for input in self.inputs.iter() {
-            hasher.update(&input.prev_txid);
-            hasher.update(input.vout.to_string());
-            hasher.update(&input.signature);
-            hasher.update(input.sequence.to_string());
+        hex::encode(dhash256(&self.to_bytes()))
+    }
+
+    pub fn txid(&self) -> &str {
+        &self.txid
+    }
+
+    pub fn inputs(&self) -> impl Iterator<Item = &TxIn> {
+        self.inputs.iter()
+    }
+
+    pub fn outputs(&self) -> impl Iterator<Item = &TxOut> {
+        self.outputs.iter()
+    }
+
+    /// A transaction with no inputs is a reward-style transaction (the role
+    /// a coinbase transaction plays in Bitcoin) and is exempt from UTXO
+    /// input validation.
+    pub fn is_coinbase(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    /// The digest signed for input `index`: the transaction's serialized
+    /// bytes with every input's signature cleared, mirroring Bitcoin's
+    /// SIGHASH_ALL, which blanks every other input's script. Clearing only
+    /// the target input would leave each input's signature committing to
+    /// every other (still-unsigned) input's signature bytes, so signing one
+    /// input would invalidate any input already signed.
+    pub fn sighash(&self, index: usize) -> Result<[u8; 32], &'static str> {
+        let inputs: Vec<&TxIn> = self.inputs.iter().collect();
+        if index >= inputs.len() {
+            return Result::Err("input index out of range");
+        }
+
+        let mut buf = Vec::new();
+        encode_compact_size(inputs.len() as u64, &mut buf);
+        for input in &inputs {
+            let cleared = TxIn::new(
+                input.prev_txid.clone(),
+                input.vout,
+                String::new(),
+                input.sequence,
+            );
+            cleared.encode(&mut buf);
+        }
+
+        let outputs: Vec<&TxOut> = self.outputs.iter().collect();
+        encode_compact_size(outputs.len() as u64, &mut buf);
+        for output in &outputs {
+            output.encode(&mut buf);
+        }
+
+        Result::Ok(dhash256(&buf))
+    }
+
+    /// Signs input `index` over its sighash and stores the signature (and
+    /// the signing public key, needed by `verify_input` to recover the
+    /// address) in the input's `signature` field, the same way a classic
+    /// Bitcoin scriptSig carries both `<sig> <pubkey>`.
+    pub fn sign_input(&mut self, index: usize, secret_key: &SecretKey) -> Result<(), &'static str> {
+        let digest = match self.sighash(index) {
+            Result::Ok(digest) => digest,
+            Result::Err(err) => return Result::Err(err),
+        };
+
+        let secp = Secp256k1::new();
+        let public_key = PublicKey::from_secret_key(&secp, secret_key);
+        let signature = wallet::sign(secret_key, &digest);
+
+        let mut encoded_signature = hex::encode(signature);
+        encoded_signature.push_str(&hex::encode(public_key.serialize()));
+
+        let updated_inputs: Vec<TxIn> = self
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| {
+                if i == index {
+                    TxIn::new(
+                        input.prev_txid.clone(),
+                        input.vout,
+                        encoded_signature.clone(),
+                        input.sequence,
+                    )
+                } else {
+                    input.clone()
+                }
+            })
+            .collect();
+
+        let mut inputs = List::new();
+        for input in updated_inputs.into_iter().rev() {
+            inputs.push_front(input);
+        }
+        self.inputs = inputs;
+        self.txid = self.calculate_txid();
+
+        Result::Ok(())
+    }
+
+    /// Checks that input `index` carries a valid signature over this
+    /// transaction's sighash from the public key committed to by the
+    /// output it spends.
+    pub fn verify_input(&self, index: usize, spent_output: &TxOut) -> bool {
+        let input = match self.inputs.iter().nth(index) {
+            Some(input) => input,
+            None => return false,
+        };
+
+        let (signature, public_key) = match decode_signature(&input.signature) {
+            Some(parts) => parts,
+            None => return false,
+        };
+
+        if wallet::public_key_to_address(&public_key) != spent_output.public_address {
+            return false;
+        }
+
+        let digest = match self.sighash(index) {
+            Result::Ok(digest) => digest,
+            Result::Err(_) => return false,
+        };
+
+        wallet::verify(&public_key, &digest, &signature)
+    }
+}
+
+/// A signed input's `signature` field is the hex-encoded concatenation of a
+/// 64-byte compact ECDSA signature and a 33-byte compressed public key, the
+/// same `<sig><pubkey>` shape a classic Bitcoin scriptSig pushes.
+fn decode_signature(encoded: &str) -> Option<([u8; 64], PublicKey)> {
+    const SIGNATURE_HEX_LEN: usize = 64 * 2;
+    const PUBLIC_KEY_HEX_LEN: usize = 33 * 2;
+
+    if encoded.len() != SIGNATURE_HEX_LEN + PUBLIC_KEY_HEX_LEN {
+        return None;
+    }
+
+    let signature_bytes = hex::decode(&encoded[..SIGNATURE_HEX_LEN]).ok()?;
+    let public_key_bytes = hex::decode(&encoded[SIGNATURE_HEX_LEN..]).ok()?;
+
+    let mut signature = [0u8; 64];
+    signature.copy_from_slice(&signature_bytes);
+    let public_key = PublicKey::from_slice(&public_key_bytes).ok()?;
+
+    Some((signature, public_key))
+}
+
+impl Encodable for Transaction {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let inputs: Vec<&TxIn> = self.inputs.iter().collect();
+        encode_compact_size(inputs.len() as u64, buf);
+        for input in &inputs {
+            input.encode(buf);
+        }
+
+        let outputs: Vec<&TxOut> = self.outputs.iter().collect();
+        encode_compact_size(outputs.len() as u64, buf);
+        for output in &outputs {
+            output.encode(buf);
+        }
+    }
+}
+
+impl Decodable for Transaction {
+    fn decode(buf: &[u8], cursor: &mut usize) -> Result<Self, &'static str> {
+        let input_count = match decode_compact_size(buf, cursor) {
+            Result::Ok(count) => count,
+            Result::Err(err) => return Result::Err(err),
+        };
+        let mut decoded_inputs = Vec::with_capacity(input_count as usize);
+        for _ in 0..input_count {
+            match TxIn::decode(buf, cursor) {
+                Result::Ok(input) => decoded_inputs.push(input),
+                Result::Err(err) => return Result::Err(err),
+            }
+        }
+
+        let output_count = match decode_compact_size(buf, cursor) {
+            Result::Ok(count) => count,
+            Result::Err(err) => return Result::Err(err),
+        };
+        let mut decoded_outputs = Vec::with_capacity(output_count as usize);
+        for _ in 0..output_count {
+            match TxOut::decode(buf, cursor) {
+                Result::Ok(output) => decoded_outputs.push(output),
+                Result::Err(err) => return Result::Err(err),
+            }
+        }
+
+        let mut inputs = List::new();
+        for input in decoded_inputs.into_iter().rev() {
+            inputs.push_front(input);
         }
-        for output in self.outputs.iter() {
-            hasher.update(&output.public_address);
-            hasher.update(output.satoshis.to_string());
+        let mut outputs = List::new();
+        for output in decoded_outputs.into_iter().rev() {
+            outputs.push_front(output);
         }
-        hex::encode(hasher.finalize())
+
+        Transaction::new(inputs, outputs)
     }
 }
 
@@ -183,6 +958,49 @@ impl TxIn {
             sequence,
         }
     }
+
+    pub fn prev_txid(&self) -> &str {
+        &self.prev_txid
+    }
+
+    pub fn vout(&self) -> usize {
+        self.vout
+    }
+}
+
+impl Encodable for TxIn {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        encode_hash(&self.prev_txid, buf).unwrap();
+        buf.extend_from_slice(&(self.vout as u32).to_le_bytes());
+        encode_var_bytes(self.signature.as_bytes(), buf);
+        buf.extend_from_slice(&self.sequence.to_le_bytes());
+    }
+}
+
+impl Decodable for TxIn {
+    fn decode(buf: &[u8], cursor: &mut usize) -> Result<Self, &'static str> {
+        let prev_txid = match decode_hash(buf, cursor) {
+            Result::Ok(hash) => hash,
+            Result::Err(err) => return Result::Err(err),
+        };
+        let vout = match read_bytes(buf, cursor, 4) {
+            Result::Ok(bytes) => u32::from_le_bytes(bytes.try_into().unwrap()) as usize,
+            Result::Err(err) => return Result::Err(err),
+        };
+        let signature = match decode_var_bytes(buf, cursor) {
+            Result::Ok(bytes) => match String::from_utf8(bytes) {
+                Ok(signature) => signature,
+                Err(_) => return Result::Err("signature is not valid utf-8"),
+            },
+            Result::Err(err) => return Result::Err(err),
+        };
+        let sequence = match read_bytes(buf, cursor, 4) {
+            Result::Ok(bytes) => u32::from_le_bytes(bytes.try_into().unwrap()),
+            Result::Err(err) => return Result::Err(err),
+        };
+
+        Result::Ok(TxIn::new(prev_txid, vout, signature, sequence))
+    }
 }
 
 impl TxOut {
@@ -192,6 +1010,39 @@ impl TxOut {
             satoshis,
         }
     }
+
+    pub fn public_address(&self) -> &str {
+        &self.public_address
+    }
+
+    pub fn satoshis(&self) -> u64 {
+        self.satoshis
+    }
+}
+
+impl Encodable for TxOut {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.satoshis.to_le_bytes());
+        encode_var_bytes(self.public_address.as_bytes(), buf);
+    }
+}
+
+impl Decodable for TxOut {
+    fn decode(buf: &[u8], cursor: &mut usize) -> Result<Self, &'static str> {
+        let satoshis = match read_bytes(buf, cursor, 8) {
+            Result::Ok(bytes) => u64::from_le_bytes(bytes.try_into().unwrap()),
+            Result::Err(err) => return Result::Err(err),
+        };
+        let public_address = match decode_var_bytes(buf, cursor) {
+            Result::Ok(bytes) => match String::from_utf8(bytes) {
+                Ok(address) => address,
+                Err(_) => return Result::Err("public_address is not valid utf-8"),
+            },
+            Result::Err(err) => return Result::Err(err),
+        };
+
+        Result::Ok(TxOut::new(public_address, satoshis))
+    }
 }
 
 #[cfg(test)]
@@ -199,6 +1050,7 @@ mod tests {
     use super::*;
 
     use crate::linked_list::LinkedList as List;
+    use crate::wallet::Wallet;
 
     #[test]
     fn test_txin() {
@@ -225,7 +1077,7 @@ mod tests {
     fn test_transaction() {
         let mut inputs = List::new();
         inputs.push_front(TxIn::new(
-            "prev_txid".to_string(),
+            "1111111111111111111111111111111111111111111111111111111111111111".to_string(),
             0,
             "script_sig".to_string(),
             0xffffffff,
@@ -234,21 +1086,39 @@ mod tests {
         let mut outputs = List::new();
         outputs.push_front(TxOut::new("script_pubkey".to_string(), 50_000_000));
 
-        let tx = Transaction::new(inputs, outputs);
+        let tx = Transaction::new(inputs, outputs).unwrap();
         assert!(!tx.txid.is_empty());
         assert_eq!(tx.inputs.iter().count(), 1);
         assert_eq!(tx.outputs.iter().count(), 1);
     }
 
     #[test]
-    fn test_block() {
-        let block = Block::new(
+    fn test_transaction_new_rejects_a_non_hex_prev_txid() {
+        let mut inputs = List::new();
+        inputs.push_front(TxIn::new(
+            "not a real txid".to_string(),
             0,
-            "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
-            1231006505,
-            "merkle_root".to_string(),
+            String::new(),
+            0xffffffff,
+        ));
+        let outputs = List::new();
+
+        assert!(Transaction::new(inputs, outputs).is_err());
+    }
+
+    #[test]
+    fn test_block_new_rejects_a_non_hex_prev_block_hash() {
+        assert!(Block::new(1, "not a real hash".to_string(), 1231006505, 0).is_err());
+    }
+
+    #[test]
+    fn test_block() {
+        let block = Block::new(
+            0,
+            "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
+            1231006505,
             2083236893,
-        );
+        ).unwrap();
 
         assert!(!block.hash.is_empty());
         assert_eq!(block.height, 0);
@@ -257,7 +1127,11 @@ mod tests {
             "0000000000000000000000000000000000000000000000000000000000000000"
         );
         assert_eq!(block.timestamp, 1231006505);
-        assert_eq!(block.merkle_root, "merkle_root");
+        // An empty block has a well-defined all-zero Merkle root.
+        assert_eq!(
+            block.merkle_root,
+            "0000000000000000000000000000000000000000000000000000000000000000"
+        );
         assert_eq!(block.nonce, 2083236893);
     }
 
@@ -267,14 +1141,54 @@ mod tests {
             0,
             "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
             1231006505,
-            "merkle_root".to_string(),
             2083236893,
-        );
+        ).unwrap();
 
-        let tx = Transaction::new(List::new(), List::new());
+        let tx = Transaction::new(List::new(), List::new()).unwrap();
+        let expected_root = tx.txid.clone();
         let result = block.add_transaction(tx);
         assert!(result.is_ok());
         assert_eq!(block.transactions.iter().count(), 1);
+        // A single-transaction block's root equals that transaction's hash.
+        assert_eq!(block.merkle_root, expected_root);
+    }
+
+    #[test]
+    fn test_compute_merkle_root_odd_transaction_count() {
+        let mut block = Block::new(
+            0,
+            "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
+            1231006505,
+            2083236893,
+        ).unwrap();
+
+        block
+            .add_transaction(Transaction::new(List::new(), List::new()).unwrap())
+            .unwrap();
+        block
+            .add_transaction(Transaction::new(
+                {
+                    let mut inputs = List::new();
+                    inputs.push_front(TxIn::new("2222222222222222222222222222222222222222222222222222222222222222".to_string(), 0, "sig".to_string(), 0));
+                    inputs
+                },
+                List::new(),
+            ).unwrap())
+            .unwrap();
+        block
+            .add_transaction(Transaction::new(
+                List::new(),
+                {
+                    let mut outputs = List::new();
+                    outputs.push_front(TxOut::new("addr".to_string(), 1));
+                    outputs
+                },
+            ).unwrap())
+            .unwrap();
+
+        assert_eq!(block.transactions.iter().count(), 3);
+        assert_eq!(block.merkle_root.len(), 64);
+        assert_eq!(block.merkle_root, block.compute_merkle_root());
     }
 
     #[test]
@@ -286,9 +1200,8 @@ mod tests {
             0,
             "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
             1231006505,
-            "merkle_root".to_string(),
             2083236893,
-        );
+        ).unwrap();
         let result = blockchain.add_block(genesis_block);
         assert!(result.is_ok());
         assert_eq!(blockchain.get_block_count(), 1);
@@ -312,9 +1225,8 @@ mod tests {
             0,
             "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
             1231006505,
-            "merkle_root_0".to_string(),
             2083236893,
-        );
+        ).unwrap();
         blockchain.add_block(genesis_block).unwrap();
 
         // Add more blocks
@@ -324,9 +1236,8 @@ mod tests {
                 i,
                 prev_hash,
                 1231006505 + i * 600,
-                format!("merkle_root_{}", i),
                 2083236893 + i as u32,
-            );
+            ).unwrap();
             blockchain.add_block(block).unwrap();
         }
 
@@ -349,21 +1260,610 @@ mod tests {
             0,
             "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
             1231006505,
-            "merkle_root_0".to_string(),
             2083236893,
-        );
+        ).unwrap();
         blockchain.add_block(genesis_block).unwrap();
 
         // Try to add an invalid block (wrong previous hash)
         let invalid_block = Block::new(
             1,
-            "invalid_previous_hash".to_string(),
+            "3333333333333333333333333333333333333333333333333333333333333333".to_string(),
             1231006505 + 600,
-            "merkle_root_1".to_string(),
             2083236894,
-        );
+        ).unwrap();
         let result = blockchain.add_block(invalid_block);
         assert!(result.is_err());
         assert_eq!(blockchain.get_block_count(), 1);
     }
+
+    fn reward_tx(public_address: &str, satoshis: u64) -> Transaction {
+        let mut outputs = List::new();
+        outputs.push_front(TxOut::new(public_address.to_string(), satoshis));
+        Transaction::new(List::new(), outputs).unwrap()
+    }
+
+    #[test]
+    fn test_utxo_set_tracks_coinbase_outputs() {
+        let mut blockchain = BlockChain::new();
+
+        let mut genesis_block = Block::new(
+            0,
+            "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
+            1231006505,
+            2083236893,
+        ).unwrap();
+        let reward = reward_tx("alice", 50_00000000);
+        let reward_txid = reward.txid().to_string();
+        genesis_block.add_transaction(reward).unwrap();
+        blockchain.add_block(genesis_block).unwrap();
+
+        assert_eq!(blockchain.get_balance("alice"), 50_00000000);
+        assert!(blockchain.get_utxo(&reward_txid, 0).is_some());
+    }
+
+    #[test]
+    fn test_spending_a_utxo_moves_the_balance() {
+        let mut blockchain = BlockChain::new();
+        let alice = Wallet::new();
+        let bob = Wallet::new();
+
+        let mut genesis_block = Block::new(
+            0,
+            "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
+            1231006505,
+            2083236893,
+        ).unwrap();
+        let reward = reward_tx(&alice.address(), 50_00000000);
+        let reward_txid = reward.txid().to_string();
+        genesis_block.add_transaction(reward).unwrap();
+        blockchain.add_block(genesis_block).unwrap();
+
+        let mut spend_block = Block::new(
+            1,
+            blockchain.get_best_block_hash().unwrap(),
+            1231006505 + 600,
+            2083236894,
+        ).unwrap();
+        let mut inputs = List::new();
+        inputs.push_front(TxIn::new(reward_txid, 0, String::new(), 0xffffffff));
+        let mut outputs = List::new();
+        outputs.push_front(TxOut::new(bob.address(), 50_00000000));
+        let mut spend = Transaction::new(inputs, outputs).unwrap();
+        spend.sign_input(0, alice.secret_key()).unwrap();
+        spend_block.add_transaction(spend).unwrap();
+
+        let result = blockchain.add_block(spend_block);
+        assert!(result.is_ok());
+        assert_eq!(blockchain.get_balance(&alice.address()), 0);
+        assert_eq!(blockchain.get_balance(&bob.address()), 50_00000000);
+    }
+
+    #[test]
+    fn test_spending_an_unknown_outpoint_is_rejected() {
+        let mut blockchain = BlockChain::new();
+
+        let genesis_block = Block::new(
+            0,
+            "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
+            1231006505,
+            2083236893,
+        ).unwrap();
+        blockchain.add_block(genesis_block).unwrap();
+
+        let mut spend_block = Block::new(
+            1,
+            blockchain.get_best_block_hash().unwrap(),
+            1231006505 + 600,
+            2083236894,
+        ).unwrap();
+        let mut inputs = List::new();
+        inputs.push_front(TxIn::new(
+            "4444444444444444444444444444444444444444444444444444444444444444".to_string(),
+            0,
+            "signature".to_string(),
+            0xffffffff,
+        ));
+        let mut outputs = List::new();
+        outputs.push_front(TxOut::new("bob".to_string(), 1));
+        let spend = Transaction::new(inputs, outputs).unwrap();
+        spend_block.add_transaction(spend).unwrap();
+
+        let result = blockchain.add_block(spend_block);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_overspending_a_utxo_is_rejected() {
+        let mut blockchain = BlockChain::new();
+
+        let mut genesis_block = Block::new(
+            0,
+            "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
+            1231006505,
+            2083236893,
+        ).unwrap();
+        let reward = reward_tx("alice", 100);
+        let reward_txid = reward.txid().to_string();
+        genesis_block.add_transaction(reward).unwrap();
+        blockchain.add_block(genesis_block).unwrap();
+
+        let mut spend_block = Block::new(
+            1,
+            blockchain.get_best_block_hash().unwrap(),
+            1231006505 + 600,
+            2083236894,
+        ).unwrap();
+        let mut inputs = List::new();
+        inputs.push_front(TxIn::new(reward_txid, 0, "signature".to_string(), 0xffffffff));
+        let mut outputs = List::new();
+        // Spending 100 satoshis into 200 is an overspend.
+        outputs.push_front(TxOut::new("bob".to_string(), 200));
+        let spend = Transaction::new(inputs, outputs).unwrap();
+        spend_block.add_transaction(spend).unwrap();
+
+        let result = blockchain.add_block(spend_block);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_mine_finds_a_hash_under_target() {
+        let mut block = Block::new(
+            0,
+            "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
+            1231006505,
+            0,
+        ).unwrap();
+
+        // The 62-digit hex is left-padded to 0x000fff..., so the hash needs
+        // three leading zero nibbles, easy enough to find quickly.
+        let target =
+            U256::from_be_hex("0fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff");
+        block.mine(target);
+
+        assert!(U256::from_be_hex(&block.hash) <= target);
+    }
+
+    #[test]
+    fn test_is_valid_block_rejects_hash_above_target() {
+        let blockchain = BlockChain::new();
+
+        let mut block = Block::new(
+            0,
+            "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
+            1231006505,
+            2083236893,
+        ).unwrap();
+        // An impossibly strict target that the unmined block's hash can't
+        // satisfy.
+        block.bits = Some(U256::ZERO.to_compact());
+
+        assert!(!blockchain.is_valid_block(&block));
+    }
+
+    #[test]
+    fn test_next_target_holds_steady_within_a_retarget_period() {
+        let mut blockchain = BlockChain::new();
+        let genesis_block = Block::new(
+            0,
+            "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
+            1231006505,
+            2083236893,
+        ).unwrap();
+        blockchain.add_block(genesis_block).unwrap();
+
+        // An unmined genesis block carries no target of its own, so the
+        // next target falls back to the easiest representable one.
+        assert_eq!(blockchain.next_target(), U256::from_compact(0x207fffff));
+    }
+
+    #[test]
+    fn test_next_target_retargets_after_interval() {
+        let mut blockchain = BlockChain::new();
+
+        let target = U256::from_be_hex(
+            "00ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+        );
+        let mut prev_hash =
+            "0000000000000000000000000000000000000000000000000000000000000000".to_string();
+
+        // Blocks arrive twice as fast as desired, so the retarget at the
+        // end of this period should tighten the target.
+        for i in 0..RETARGET_INTERVAL {
+            let mut block = Block::new(i, prev_hash.clone(), 1231006505 + i * 300, 0).unwrap();
+            block.mine(target);
+            prev_hash = block.hash.clone();
+            blockchain.add_block(block).unwrap();
+        }
+
+        let retargeted = blockchain.next_target();
+        assert!(retargeted < target);
+    }
+
+    #[test]
+    fn test_next_target_saturates_instead_of_overflowing_near_max_target() {
+        let mut blockchain = BlockChain::new();
+        let max_target = U256::from_compact(MAX_TARGET_BITS);
+        let mut prev_hash =
+            "0000000000000000000000000000000000000000000000000000000000000000".to_string();
+
+        // Blocks arrive four times slower than desired, so the retarget
+        // multiplies the near-maximum tip target by the measured 21600-second
+        // span (nine 2400-second gaps, 3.6x the desired 6000) — enough to
+        // overflow 256 bits if the multiply doesn't saturate.
+        for i in 0..RETARGET_INTERVAL {
+            let mut block = Block::new(i, prev_hash.clone(), 1231006505 + i * 2400, 0).unwrap();
+            block.mine(max_target);
+            prev_hash = block.hash.clone();
+            blockchain.add_block(block).unwrap();
+        }
+
+        let desired_timespan = RETARGET_INTERVAL * BLOCK_SPACING_SECONDS;
+        assert_eq!(
+            blockchain.next_target(),
+            U256::MAX.div_u64(desired_timespan)
+        );
+    }
+
+    #[test]
+    fn test_txout_bytes_round_trip() {
+        let txout = TxOut::new("bc1q_some_address".to_string(), 50_00000000);
+        let bytes = txout.to_bytes();
+        let decoded = TxOut::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.public_address(), txout.public_address());
+        assert_eq!(decoded.satoshis(), txout.satoshis());
+    }
+
+    #[test]
+    fn test_txin_bytes_round_trip() {
+        let txin = TxIn::new(
+            hex::encode([0x11u8; 32]),
+            7,
+            "signature".to_string(),
+            0xffffffff,
+        );
+        let bytes = txin.to_bytes();
+        let decoded = TxIn::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.prev_txid(), txin.prev_txid());
+        assert_eq!(decoded.vout(), txin.vout());
+    }
+
+    #[test]
+    fn test_transaction_bytes_round_trip() {
+        let mut inputs = List::new();
+        inputs.push_front(TxIn::new(
+            hex::encode([0x22u8; 32]),
+            1,
+            "signature".to_string(),
+            0xffffffff,
+        ));
+        let mut outputs = List::new();
+        outputs.push_front(TxOut::new("alice".to_string(), 12345));
+        let tx = Transaction::new(inputs, outputs).unwrap();
+
+        let bytes = tx.to_bytes();
+        let decoded = Transaction::from_bytes(&bytes).unwrap();
+
+        assert_eq!(decoded.txid(), tx.txid());
+        assert_eq!(decoded.inputs().count(), 1);
+        assert_eq!(decoded.outputs().count(), 1);
+    }
+
+    #[test]
+    fn test_block_bytes_round_trip() {
+        let mut block = Block::new(
+            5,
+            "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
+            1231006505,
+            42,
+        ).unwrap();
+        block
+            .add_transaction(reward_tx("alice", 50_00000000))
+            .unwrap();
+
+        let bytes = block.to_bytes();
+        let decoded = Block::from_bytes(&bytes).unwrap();
+
+        assert_eq!(decoded.hash, block.hash);
+        assert_eq!(decoded.height, block.height);
+        assert_eq!(decoded.merkle_root, block.merkle_root);
+        assert_eq!(decoded.transactions().count(), 1);
+    }
+
+    #[test]
+    fn test_calculate_txid_changes_with_serialized_fields() {
+        let tx_a = reward_tx("alice", 100);
+        let tx_b = reward_tx("alice", 200);
+        assert_ne!(tx_a.txid(), tx_b.txid());
+    }
+
+    #[test]
+    fn test_a_side_branch_with_more_work_becomes_active() {
+        let mut blockchain = BlockChain::new();
+
+        let genesis_hash =
+            "0000000000000000000000000000000000000000000000000000000000000000".to_string();
+        let easy_target = U256::from_be_hex(
+            "0fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+        );
+        let hard_target = U256::from_be_hex(
+            "00ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+        );
+
+        let mut genesis_block = Block::new(0, genesis_hash, 1231006505, 0).unwrap();
+        genesis_block.mine(easy_target);
+        let genesis_hash = genesis_block.hash.clone();
+        blockchain.add_block(genesis_block).unwrap();
+
+        // The chain everyone sees first: easier target, arrives first.
+        let mut weak_tip = Block::new(1, genesis_hash.clone(), 1231006505 + 600, 0).unwrap();
+        weak_tip
+            .add_transaction(reward_tx("alice", 50_00000000))
+            .unwrap();
+        weak_tip.mine(easy_target);
+        let weak_tip_hash = weak_tip.hash.clone();
+        blockchain.add_block(weak_tip).unwrap();
+
+        assert_eq!(blockchain.get_best_block_hash(), Some(weak_tip_hash));
+        assert_eq!(blockchain.get_balance("alice"), 50_00000000);
+
+        // A competing block at the same height, mined against a stricter
+        // target, carries more cumulative work and should take over.
+        let mut strong_tip = Block::new(1, genesis_hash, 1231006505 + 600, 0).unwrap();
+        strong_tip
+            .add_transaction(reward_tx("bob", 50_00000000))
+            .unwrap();
+        strong_tip.mine(hard_target);
+        let strong_tip_hash = strong_tip.hash.clone();
+        blockchain.add_block(strong_tip).unwrap();
+
+        assert_eq!(blockchain.get_best_block_hash(), Some(strong_tip_hash));
+        // The reorg should have undone alice's block and applied bob's.
+        assert_eq!(blockchain.get_balance("alice"), 0);
+        assert_eq!(blockchain.get_balance("bob"), 50_00000000);
+        assert_eq!(blockchain.get_block_count(), 2);
+    }
+
+    #[test]
+    fn test_reorganize_onto_a_shorter_branch_prunes_stale_heights() {
+        let mut blockchain = BlockChain::new();
+
+        let easy_target = U256::from_be_hex(
+            "0fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+        );
+        let hard_target = U256::from_be_hex(
+            "00ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+        );
+
+        let mut genesis_block = Block::new(
+            0,
+            "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
+            1231006505,
+            0,
+        )
+        .unwrap();
+        genesis_block.mine(easy_target);
+        let mut prev_hash = genesis_block.hash.clone();
+        blockchain.add_block(genesis_block).unwrap();
+
+        // Build a long, low-work chain up to height 3.
+        for height in 1..=3u64 {
+            let mut block =
+                Block::new(height, prev_hash.clone(), 1231006505 + height * 600, 0).unwrap();
+            block.mine(easy_target);
+            prev_hash = block.hash.clone();
+            blockchain.add_block(block).unwrap();
+        }
+        assert_eq!(blockchain.get_block_count(), 4);
+
+        // A short, high-work branch off genesis carries more cumulative work
+        // and should take over as the active chain.
+        let mut strong_tip =
+            Block::new(1, blockchain.get_block_by_height(0).unwrap().hash.clone(), 1231006505 + 600, 0)
+                .unwrap();
+        strong_tip.mine(hard_target);
+        let strong_tip_hash = strong_tip.hash.clone();
+        blockchain.add_block(strong_tip).unwrap();
+
+        assert_eq!(blockchain.get_best_block_hash(), Some(strong_tip_hash));
+        assert_eq!(blockchain.get_block_count(), 2);
+        assert!(blockchain.get_block_by_height(2).is_none());
+        assert!(blockchain.get_block_by_height(3).is_none());
+    }
+
+    fn spendable_input(prev_txid: &str) -> TxIn {
+        TxIn::new(prev_txid.to_string(), 0, String::new(), 0xffffffff)
+    }
+
+    #[test]
+    fn test_sign_input_and_verify_input_round_trip() {
+        let alice = Wallet::new();
+        let reward = reward_tx(&alice.address(), 1_000);
+
+        let mut inputs = List::new();
+        inputs.push_front(spendable_input(reward.txid()));
+        let mut outputs = List::new();
+        outputs.push_front(TxOut::new("bob".to_string(), 1_000));
+        let mut spend = Transaction::new(inputs, outputs).unwrap();
+        spend.sign_input(0, alice.secret_key()).unwrap();
+
+        let spent_output = reward.outputs().next().unwrap();
+        assert!(spend.verify_input(0, spent_output));
+    }
+
+    #[test]
+    fn test_signing_one_input_does_not_invalidate_another() {
+        let alice = Wallet::new();
+        let reward_a = reward_tx(&alice.address(), 1_000);
+        let reward_b = reward_tx(&alice.address(), 2_000);
+
+        let mut inputs = List::new();
+        inputs.push_front(spendable_input(reward_b.txid()));
+        inputs.push_front(spendable_input(reward_a.txid()));
+        let mut outputs = List::new();
+        outputs.push_front(TxOut::new("bob".to_string(), 3_000));
+        let mut spend = Transaction::new(inputs, outputs).unwrap();
+
+        spend.sign_input(0, alice.secret_key()).unwrap();
+        spend.sign_input(1, alice.secret_key()).unwrap();
+
+        let output_a = reward_a.outputs().next().unwrap();
+        let output_b = reward_b.outputs().next().unwrap();
+        assert!(spend.verify_input(0, output_a));
+        assert!(spend.verify_input(1, output_b));
+    }
+
+    #[test]
+    fn test_verify_input_rejects_signature_from_the_wrong_key() {
+        let alice = Wallet::new();
+        let mallory = Wallet::new();
+        let reward = reward_tx(&alice.address(), 1_000);
+
+        let mut inputs = List::new();
+        inputs.push_front(spendable_input(reward.txid()));
+        let mut outputs = List::new();
+        outputs.push_front(TxOut::new("bob".to_string(), 1_000));
+        let mut spend = Transaction::new(inputs, outputs).unwrap();
+        // Mallory signs, but the output she's trying to spend belongs to alice.
+        spend.sign_input(0, mallory.secret_key()).unwrap();
+
+        let spent_output = reward.outputs().next().unwrap();
+        assert!(!spend.verify_input(0, spent_output));
+    }
+
+    #[test]
+    fn test_verify_input_rejects_a_tampered_output() {
+        let alice = Wallet::new();
+        let reward = reward_tx(&alice.address(), 1_000);
+
+        let mut inputs = List::new();
+        inputs.push_front(spendable_input(reward.txid()));
+        let mut outputs = List::new();
+        outputs.push_front(TxOut::new("bob".to_string(), 1_000));
+        let mut spend = Transaction::new(inputs, outputs).unwrap();
+        spend.sign_input(0, alice.secret_key()).unwrap();
+
+        // A signature is only valid against its own transaction's sighash.
+        let mut other_inputs = List::new();
+        other_inputs.push_front(spendable_input(reward.txid()));
+        let mut other_outputs = List::new();
+        other_outputs.push_front(TxOut::new("mallory".to_string(), 1_000));
+        let tampered = Transaction::new(other_inputs, other_outputs).unwrap();
+
+        let spent_output = reward.outputs().next().unwrap();
+        assert!(!tampered.verify_input(0, spent_output));
+    }
+
+    #[test]
+    fn test_spending_with_an_invalid_signature_is_rejected() {
+        let mut blockchain = BlockChain::new();
+        let alice = Wallet::new();
+
+        let mut genesis_block = Block::new(
+            0,
+            "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
+            1231006505,
+            2083236893,
+        ).unwrap();
+        let reward = reward_tx(&alice.address(), 1_000);
+        let reward_txid = reward.txid().to_string();
+        genesis_block.add_transaction(reward).unwrap();
+        blockchain.add_block(genesis_block).unwrap();
+
+        let mut spend_block = Block::new(
+            1,
+            blockchain.get_best_block_hash().unwrap(),
+            1231006505 + 600,
+            2083236894,
+        ).unwrap();
+        let mut inputs = List::new();
+        inputs.push_front(TxIn::new(
+            reward_txid,
+            0,
+            "not a real signature".to_string(),
+            0xffffffff,
+        ));
+        let mut outputs = List::new();
+        outputs.push_front(TxOut::new("bob".to_string(), 1_000));
+        let spend = Transaction::new(inputs, outputs).unwrap();
+        spend_block.add_transaction(spend).unwrap();
+
+        let result = blockchain.add_block(spend_block);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_merkle_proof_verifies_for_every_transaction() {
+        let mut block = Block::new(
+            0,
+            "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
+            1231006505,
+            2083236893,
+        ).unwrap();
+        block
+            .add_transaction(reward_tx("alice", 1))
+            .unwrap();
+        block
+            .add_transaction(reward_tx("bob", 2))
+            .unwrap();
+        block
+            .add_transaction(reward_tx("carol", 3))
+            .unwrap();
+
+        let txids: Vec<String> = block.transactions().map(|tx| tx.txid().to_string()).collect();
+        assert_eq!(txids.len(), 3);
+
+        for txid in &txids {
+            let proof = block.merkle_proof(txid).unwrap();
+            assert!(proof.verify(txid, &block.merkle_root));
+        }
+    }
+
+    #[test]
+    fn test_merkle_proof_single_transaction_block_has_no_steps() {
+        let mut block = Block::new(
+            0,
+            "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
+            1231006505,
+            2083236893,
+        ).unwrap();
+        let tx = reward_tx("alice", 1);
+        let txid = tx.txid().to_string();
+        block.add_transaction(tx).unwrap();
+
+        let proof = block.merkle_proof(&txid).unwrap();
+        assert!(proof.steps.is_empty());
+        assert!(proof.verify(&txid, &block.merkle_root));
+    }
+
+    #[test]
+    fn test_merkle_proof_returns_none_for_unknown_txid() {
+        let mut block = Block::new(
+            0,
+            "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
+            1231006505,
+            2083236893,
+        ).unwrap();
+        block.add_transaction(reward_tx("alice", 1)).unwrap();
+
+        assert!(block
+            .merkle_proof("5555555555555555555555555555555555555555555555555555555555555555")
+            .is_none());
+    }
+
+    #[test]
+    fn test_merkle_proof_rejects_wrong_root() {
+        let mut block = Block::new(
+            0,
+            "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
+            1231006505,
+            2083236893,
+        ).unwrap();
+        block.add_transaction(reward_tx("alice", 1)).unwrap();
+        block.add_transaction(reward_tx("bob", 2)).unwrap();
+
+        let txid = block.transactions().next().unwrap().txid().to_string();
+        let proof = block.merkle_proof(&txid).unwrap();
+
+        assert!(!proof.verify(&txid, "00"));
+    }
 }
@@ -0,0 +1,191 @@
+use crate::mresult::MResult as Result;
+
+/// A type that can be serialized into Bitcoin's little-endian consensus
+/// wire format.
+pub trait Encodable {
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.encode(&mut buf);
+        buf
+    }
+}
+
+/// A type that can be parsed back out of Bitcoin's consensus wire format.
+pub trait Decodable: Sized {
+    fn decode(buf: &[u8], cursor: &mut usize) -> Result<Self, &'static str>;
+
+    fn from_bytes(buf: &[u8]) -> Result<Self, &'static str> {
+        let mut cursor = 0;
+        Self::decode(buf, &mut cursor)
+    }
+}
+
+/// Reverses a 32-byte hash, the convention Bitcoin uses for storing txids
+/// and other double-SHA256 digests inside serialized structures.
+fn reverse_bytes(mut bytes: [u8; 32]) -> [u8; 32] {
+    bytes.reverse();
+    bytes
+}
+
+/// Hex-decodes a hash and appends it reversed, as Bitcoin's wire format
+/// requires.
+pub fn encode_hash(hex_hash: &str, buf: &mut Vec<u8>) -> Result<(), &'static str> {
+    let bytes = match hex::decode(hex_hash) {
+        Ok(bytes) => bytes,
+        Err(_) => return Result::Err("hash is not valid hex"),
+    };
+    if bytes.len() != 32 {
+        return Result::Err("hash must be 32 bytes");
+    }
+    let mut array = [0u8; 32];
+    array.copy_from_slice(&bytes);
+    buf.extend_from_slice(&reverse_bytes(array));
+    Result::Ok(())
+}
+
+/// Reads a reversed 32-byte hash off the wire and hex-encodes it back into
+/// the natural (non-reversed) form this crate stores hashes in.
+pub fn decode_hash(buf: &[u8], cursor: &mut usize) -> Result<String, &'static str> {
+    let bytes = match read_bytes(buf, cursor, 32) {
+        Result::Ok(bytes) => bytes,
+        Result::Err(err) => return Result::Err(err),
+    };
+    let mut array = [0u8; 32];
+    array.copy_from_slice(bytes);
+    Result::Ok(hex::encode(reverse_bytes(array)))
+}
+
+pub fn read_bytes<'a>(
+    buf: &'a [u8],
+    cursor: &mut usize,
+    len: usize,
+) -> Result<&'a [u8], &'static str> {
+    if *cursor + len > buf.len() {
+        return Result::Err("unexpected end of buffer");
+    }
+    let slice = &buf[*cursor..*cursor + len];
+    *cursor += len;
+    Result::Ok(slice)
+}
+
+/// Encodes `value` using Bitcoin's CompactSize varint: values under 0xfd
+/// encode as a single byte, larger values are prefixed with 0xfd/0xfe/0xff
+/// followed by a fixed-width little-endian integer.
+pub fn encode_compact_size(value: u64, buf: &mut Vec<u8>) {
+    if value < 0xfd {
+        buf.push(value as u8);
+    } else if value <= u16::MAX as u64 {
+        buf.push(0xfd);
+        buf.extend_from_slice(&(value as u16).to_le_bytes());
+    } else if value <= u32::MAX as u64 {
+        buf.push(0xfe);
+        buf.extend_from_slice(&(value as u32).to_le_bytes());
+    } else {
+        buf.push(0xff);
+        buf.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+pub fn decode_compact_size(buf: &[u8], cursor: &mut usize) -> Result<u64, &'static str> {
+    let prefix = match read_bytes(buf, cursor, 1) {
+        Result::Ok(bytes) => bytes[0],
+        Result::Err(err) => return Result::Err(err),
+    };
+
+    match prefix {
+        0xfd => match read_bytes(buf, cursor, 2) {
+            Result::Ok(bytes) => Result::Ok(u16::from_le_bytes(bytes.try_into().unwrap()) as u64),
+            Result::Err(err) => Result::Err(err),
+        },
+        0xfe => match read_bytes(buf, cursor, 4) {
+            Result::Ok(bytes) => Result::Ok(u32::from_le_bytes(bytes.try_into().unwrap()) as u64),
+            Result::Err(err) => Result::Err(err),
+        },
+        0xff => match read_bytes(buf, cursor, 8) {
+            Result::Ok(bytes) => Result::Ok(u64::from_le_bytes(bytes.try_into().unwrap())),
+            Result::Err(err) => Result::Err(err),
+        },
+        n => Result::Ok(n as u64),
+    }
+}
+
+/// Encodes a variable-length byte string as a CompactSize length prefix
+/// followed by the raw bytes, the layout Bitcoin uses for scripts and
+/// other free-form fields.
+pub fn encode_var_bytes(bytes: &[u8], buf: &mut Vec<u8>) {
+    encode_compact_size(bytes.len() as u64, buf);
+    buf.extend_from_slice(bytes);
+}
+
+pub fn decode_var_bytes(buf: &[u8], cursor: &mut usize) -> Result<Vec<u8>, &'static str> {
+    let len = match decode_compact_size(buf, cursor) {
+        Result::Ok(len) => len as usize,
+        Result::Err(err) => return Result::Err(err),
+    };
+    match read_bytes(buf, cursor, len) {
+        Result::Ok(bytes) => Result::Ok(bytes.to_vec()),
+        Result::Err(err) => Result::Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compact_size_round_trip_single_byte() {
+        let mut buf = Vec::new();
+        encode_compact_size(42, &mut buf);
+        assert_eq!(buf, vec![42]);
+
+        let mut cursor = 0;
+        assert_eq!(decode_compact_size(&buf, &mut cursor).unwrap(), 42);
+        assert_eq!(cursor, buf.len());
+    }
+
+    #[test]
+    fn test_compact_size_round_trip_wide_values() {
+        for value in [0xfcu64, 0xfd, 0xffff, 0x1_0000, 0xffff_ffff, 0x1_0000_0000] {
+            let mut buf = Vec::new();
+            encode_compact_size(value, &mut buf);
+
+            let mut cursor = 0;
+            assert_eq!(decode_compact_size(&buf, &mut cursor).unwrap(), value);
+            assert_eq!(cursor, buf.len());
+        }
+    }
+
+    #[test]
+    fn test_hash_round_trip_is_reversed_on_the_wire() {
+        let hash = "0011223344556677889900112233445566778899001122334455667788990011";
+        let mut buf = Vec::new();
+        encode_hash(hash, &mut buf).unwrap();
+
+        // The wire bytes are the byte-reversal of the hex-decoded hash.
+        let mut expected = hex::decode(hash).unwrap();
+        expected.reverse();
+        assert_eq!(buf, expected);
+
+        let mut cursor = 0;
+        assert_eq!(decode_hash(&buf, &mut cursor).unwrap(), hash);
+    }
+
+    #[test]
+    fn test_var_bytes_round_trip() {
+        let mut buf = Vec::new();
+        encode_var_bytes(b"signature", &mut buf);
+
+        let mut cursor = 0;
+        let decoded = decode_var_bytes(&buf, &mut cursor).unwrap();
+        assert_eq!(decoded, b"signature");
+    }
+
+    #[test]
+    fn test_read_bytes_rejects_truncated_buffer() {
+        let buf = [1u8, 2, 3];
+        let mut cursor = 0;
+        assert!(read_bytes(&buf, &mut cursor, 10).is_err());
+    }
+}
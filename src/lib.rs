@@ -0,0 +1,7 @@
+pub mod block;
+pub mod encoding;
+pub mod linked_list;
+pub mod mresult;
+pub mod pow;
+pub mod utxo;
+pub mod wallet;
@@ -9,9 +9,9 @@ fn main() {
         0,
         String::from("0000000000000000000000000000000000000000000000000000000000000000"),
         1231006505,
-        String::from("4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b"),
         2083236893,
-    );
+    )
+    .unwrap();
     blockchain.add_block(genesis_block).unwrap();
 
     // Add some transactions and blocks
@@ -20,9 +20,9 @@ fn main() {
             i,
             blockchain.get_best_block_hash().unwrap(),
             1231006505 + i * 600,
-            format!("merkle_root_{}", i),
             (2083236893 + i).try_into().unwrap(),
-        );
+        )
+        .unwrap();
 
         let tx = Transaction::new(
             LinkedList::new(), // For simplicity, we're not adding inputs
@@ -31,7 +31,8 @@ fn main() {
                 outputs.push_front(TxOut::new(format!("pubkey_{}", i), 50 * 100000000));
                 outputs
             },
-        );
+        )
+        .unwrap();
 
         block.add_transaction(tx).unwrap();
         blockchain.add_block(block).unwrap();
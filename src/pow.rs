@@ -0,0 +1,351 @@
+use std::cmp::Ordering;
+
+/// An unsigned 256-bit integer used to represent proof-of-work targets.
+///
+/// Limbs are stored little-endian (`limbs[0]` is the least significant
+/// 64-bit word) so multi-limb arithmetic can carry upward through the
+/// array in index order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct U256 {
+    limbs: [u64; 4],
+}
+
+impl U256 {
+    pub const ZERO: U256 = U256 { limbs: [0; 4] };
+    pub const MAX: U256 = U256 {
+        limbs: [u64::MAX; 4],
+    };
+
+    pub fn from_u64(value: u64) -> Self {
+        U256 {
+            limbs: [value, 0, 0, 0],
+        }
+    }
+
+    pub fn from_be_bytes(bytes: [u8; 32]) -> Self {
+        let mut limbs = [0u64; 4];
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let start = (3 - i) * 8;
+            *limb = u64::from_be_bytes(bytes[start..start + 8].try_into().unwrap());
+        }
+        U256 { limbs }
+    }
+
+    pub fn to_be_bytes(&self) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        for (i, limb) in self.limbs.iter().enumerate() {
+            let start = (3 - i) * 8;
+            bytes[start..start + 8].copy_from_slice(&limb.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Parses a hex-encoded, big-endian 256-bit hash such as the ones
+    /// produced by `Block::calculate_hash`.
+    pub fn from_be_hex(hex_str: &str) -> Self {
+        let bytes = hex::decode(hex_str).expect("32-byte hex hash");
+        let mut padded = [0u8; 32];
+        let offset = 32 - bytes.len();
+        padded[offset..].copy_from_slice(&bytes);
+        U256::from_be_bytes(padded)
+    }
+
+    pub fn to_be_hex(&self) -> String {
+        hex::encode(self.to_be_bytes())
+    }
+
+    /// Decodes Bitcoin's "compact bits" difficulty target encoding: the top
+    /// byte is an exponent and the remaining 23 bits are a mantissa, such
+    /// that `target = mantissa * 256^(exponent - 3)`.
+    pub fn from_compact(bits: u32) -> Self {
+        let exponent = (bits >> 24) as i32;
+        let mantissa = (bits & 0x007f_ffff) as u64;
+
+        if mantissa == 0 || exponent > 32 {
+            return U256::ZERO;
+        }
+
+        let shift_bits = (exponent - 3) * 8;
+        if shift_bits >= 0 {
+            U256::from_u64(mantissa).shl(shift_bits as u32)
+        } else {
+            U256::from_u64(mantissa >> (-shift_bits))
+        }
+    }
+
+    /// Encodes this value back into Bitcoin's compact bits format.
+    pub fn to_compact(&self) -> u32 {
+        let bytes = self.to_be_bytes();
+        let first_nonzero = bytes.iter().position(|&b| b != 0);
+
+        let first_nonzero = match first_nonzero {
+            Some(index) => index,
+            None => return 0,
+        };
+
+        let mut size = 32 - first_nonzero;
+        let significant = &bytes[first_nonzero..];
+
+        let mut compact = if size <= 3 {
+            let mut value: u32 = 0;
+            for &b in significant {
+                value = (value << 8) | b as u32;
+            }
+            value << (8 * (3 - size))
+        } else {
+            ((significant[0] as u32) << 16)
+                | ((significant[1] as u32) << 8)
+                | (significant[2] as u32)
+        };
+
+        // If the mantissa's top bit is set it would be read as a sign bit,
+        // so shift it down a byte and grow the exponent to compensate.
+        if compact & 0x0080_0000 != 0 {
+            compact >>= 8;
+            size += 1;
+        }
+
+        compact | ((size as u32) << 24)
+    }
+
+    pub fn shl(&self, bits: u32) -> Self {
+        if bits == 0 {
+            return *self;
+        }
+        if bits >= 256 {
+            return U256::ZERO;
+        }
+
+        let limb_shift = (bits / 64) as usize;
+        let bit_shift = bits % 64;
+        let mut out = [0u64; 4];
+
+        for i in (0..4).rev() {
+            if i < limb_shift {
+                continue;
+            }
+            let mut value = self.limbs[i - limb_shift] << bit_shift;
+            if bit_shift > 0 && i - limb_shift >= 1 {
+                value |= self.limbs[i - limb_shift - 1] >> (64 - bit_shift);
+            }
+            out[i] = value;
+        }
+
+        U256 { limbs: out }
+    }
+
+    pub fn mul_u64(&self, factor: u64) -> Self {
+        let mut result = [0u64; 4];
+        let mut carry: u128 = 0;
+        for (i, limb) in self.limbs.iter().enumerate() {
+            let product = *limb as u128 * factor as u128 + carry;
+            result[i] = product as u64;
+            carry = product >> 64;
+        }
+        U256 { limbs: result }
+    }
+
+    /// Like `mul_u64`, but returns `U256::MAX` instead of silently wrapping
+    /// when the product doesn't fit in 256 bits.
+    pub fn saturating_mul_u64(&self, factor: u64) -> Self {
+        let mut result = [0u64; 4];
+        let mut carry: u128 = 0;
+        for (i, limb) in self.limbs.iter().enumerate() {
+            let product = *limb as u128 * factor as u128 + carry;
+            result[i] = product as u64;
+            carry = product >> 64;
+        }
+        if carry != 0 {
+            return U256::MAX;
+        }
+        U256 { limbs: result }
+    }
+
+    pub fn div_u64(&self, divisor: u64) -> Self {
+        assert!(divisor != 0, "division by zero");
+        let mut result = [0u64; 4];
+        let mut remainder: u128 = 0;
+        for i in (0..4).rev() {
+            let dividend = (remainder << 64) | self.limbs[i] as u128;
+            result[i] = (dividend / divisor as u128) as u64;
+            remainder = dividend % divisor as u128;
+        }
+        U256 { limbs: result }
+    }
+
+    pub fn add(&self, other: &U256) -> Self {
+        let mut result = [0u64; 4];
+        let mut carry: u128 = 0;
+        for (i, (a, b)) in self.limbs.iter().zip(other.limbs.iter()).enumerate() {
+            let sum = *a as u128 + *b as u128 + carry;
+            result[i] = sum as u64;
+            carry = sum >> 64;
+        }
+        U256 { limbs: result }
+    }
+
+    pub fn sub(&self, other: &U256) -> Self {
+        let mut result = [0u64; 4];
+        let mut borrow: i128 = 0;
+        for (i, (a, b)) in self.limbs.iter().zip(other.limbs.iter()).enumerate() {
+            let diff = *a as i128 - *b as i128 - borrow;
+            if diff < 0 {
+                result[i] = (diff + (1i128 << 64)) as u64;
+                borrow = 1;
+            } else {
+                result[i] = diff as u64;
+                borrow = 0;
+            }
+        }
+        U256 { limbs: result }
+    }
+
+    fn bit(&self, index: u32) -> bool {
+        let limb = (index / 64) as usize;
+        let offset = index % 64;
+        (self.limbs[limb] >> offset) & 1 == 1
+    }
+
+    /// Full 256-bit long division, bit by bit. Slower than `div_u64` but
+    /// needed for dividing by another 256-bit value, as proof-of-work
+    /// accounting requires.
+    pub fn div(&self, divisor: &U256) -> Self {
+        assert!(*divisor != U256::ZERO, "division by zero");
+        let mut quotient = U256::ZERO;
+        let mut remainder = U256::ZERO;
+        for i in (0..256).rev() {
+            remainder = remainder.shl(1);
+            if self.bit(i) {
+                remainder = remainder.add(&U256::from_u64(1));
+            }
+            if remainder >= *divisor {
+                remainder = remainder.sub(divisor);
+                quotient = quotient.add(&U256::from_u64(1).shl(i));
+            }
+        }
+        quotient
+    }
+
+    /// The amount of proof-of-work represented by mining a block against
+    /// this value as its target, using the same formula Bitcoin Core's
+    /// `GetBlockProof` does: `(~target / (target + 1)) + 1`. Expressing it
+    /// this way avoids needing to represent `2^256` itself, which doesn't
+    /// fit in a `U256`.
+    pub fn work(&self) -> Self {
+        let complement = U256::MAX.sub(self);
+        let divisor = self.add(&U256::from_u64(1));
+        complement.div(&divisor).add(&U256::from_u64(1))
+    }
+}
+
+impl PartialOrd for U256 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for U256 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        for i in (0..4).rev() {
+            match self.limbs[i].cmp(&other.limbs[i]) {
+                Ordering::Equal => continue,
+                ordering => return ordering,
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_u64_and_ordering() {
+        let small = U256::from_u64(1);
+        let big = U256::from_u64(u64::MAX);
+        assert!(small < big);
+        assert!(big > small);
+        assert_eq!(U256::ZERO, U256::from_u64(0));
+    }
+
+    #[test]
+    fn test_be_hex_round_trip() {
+        let hash = "0000000000000000000000000000000000000000000000000000000000002a2a";
+        let value = U256::from_be_hex(hash);
+        assert_eq!(value.to_be_hex(), hash);
+    }
+
+    #[test]
+    fn test_compact_round_trip() {
+        // Bitcoin genesis block difficulty bits.
+        let bits = 0x1d00ffffu32;
+        let target = U256::from_compact(bits);
+        assert_eq!(target.to_compact(), bits);
+    }
+
+    #[test]
+    fn test_compact_small_exponent() {
+        let bits = 0x01003456u32;
+        let target = U256::from_compact(bits);
+        assert_eq!(target, U256::from_u64(0x00));
+    }
+
+    #[test]
+    fn test_mul_and_div_u64() {
+        let value = U256::from_u64(1_000_000);
+        let doubled = value.mul_u64(2);
+        assert_eq!(doubled, U256::from_u64(2_000_000));
+        assert_eq!(doubled.div_u64(2), value);
+    }
+
+    #[test]
+    fn test_saturating_mul_u64_caps_instead_of_wrapping() {
+        let value = U256::from_u64(1_000_000);
+        assert_eq!(value.mul_u64(2), value.saturating_mul_u64(2));
+
+        let overflowing = U256::MAX.saturating_mul_u64(2);
+        assert_eq!(overflowing, U256::MAX);
+        assert_ne!(overflowing, U256::MAX.mul_u64(2));
+    }
+
+    #[test]
+    fn test_shl_crosses_limb_boundary() {
+        let value = U256::from_u64(1);
+        let shifted = value.shl(64);
+        assert!(shifted > U256::from_u64(u64::MAX));
+        assert_eq!(shifted.shl(64).shl(64).shl(64), U256::ZERO);
+    }
+
+    #[test]
+    fn test_add_and_sub_round_trip() {
+        let a = U256::from_u64(u64::MAX);
+        let b = U256::from_u64(1);
+        let sum = a.add(&b);
+        // Adding 1 to the max single-limb value carries into the next limb.
+        assert!(sum > a);
+        assert_eq!(sum.sub(&b), a);
+    }
+
+    #[test]
+    fn test_div_matches_div_u64_for_small_divisors() {
+        let value = U256::from_u64(1_000_000);
+        assert_eq!(value.div(&U256::from_u64(7)), value.div_u64(7));
+    }
+
+    #[test]
+    fn test_lower_target_means_more_work() {
+        let easy = U256::from_compact(0x207fffff);
+        let hard = U256::from_compact(0x1d00ffff);
+        assert!(hard < easy);
+        assert!(hard.work() > easy.work());
+    }
+
+    #[test]
+    fn test_work_is_additive_across_blocks() {
+        let target = U256::from_compact(0x1d00ffff);
+        let one_block = target.work();
+        let two_blocks = one_block.add(&target.work());
+        assert!(two_blocks > one_block);
+    }
+}
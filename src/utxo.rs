@@ -0,0 +1,38 @@
+use std::collections::HashMap;
+
+use crate::block::TxOut;
+
+/// The set of currently spendable transaction outputs, keyed by the
+/// outpoint (`txid`, `vout`) that created them.
+#[derive(Clone, Default)]
+pub struct UtxoSet {
+    outputs: HashMap<(String, usize), TxOut>,
+}
+
+impl UtxoSet {
+    pub fn new() -> Self {
+        UtxoSet {
+            outputs: HashMap::new(),
+        }
+    }
+
+    pub fn get_utxo(&self, txid: &str, vout: usize) -> Option<&TxOut> {
+        self.outputs.get(&(txid.to_string(), vout))
+    }
+
+    pub fn get_balance(&self, public_address: &str) -> u64 {
+        self.outputs
+            .values()
+            .filter(|txout| txout.public_address() == public_address)
+            .map(|txout| txout.satoshis())
+            .sum()
+    }
+
+    pub fn insert(&mut self, txid: String, vout: usize, txout: TxOut) {
+        self.outputs.insert((txid, vout), txout);
+    }
+
+    pub fn remove(&mut self, txid: &str, vout: usize) -> Option<TxOut> {
+        self.outputs.remove(&(txid.to_string(), vout))
+    }
+}
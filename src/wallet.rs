@@ -0,0 +1,149 @@
+use ripemd::Ripemd160;
+use secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1, SecretKey};
+use sha2::{Digest, Sha256};
+
+/// The version byte prepended to the address payload before base58check
+/// encoding, analogous to Bitcoin mainnet's `0x00` P2PKH prefix.
+const ADDRESS_VERSION: u8 = 0x00;
+
+/// A keypair that can derive its own address and sign transaction inputs.
+pub struct Wallet {
+    secret_key: SecretKey,
+    public_key: PublicKey,
+}
+
+impl Wallet {
+    /// Generates a new random keypair.
+    pub fn new() -> Self {
+        let secp = Secp256k1::new();
+        let (secret_key, public_key) = secp.generate_keypair(&mut rand::thread_rng());
+        Wallet {
+            secret_key,
+            public_key,
+        }
+    }
+
+    pub fn from_secret_key(secret_key: SecretKey) -> Self {
+        let secp = Secp256k1::new();
+        let public_key = PublicKey::from_secret_key(&secp, &secret_key);
+        Wallet {
+            secret_key,
+            public_key,
+        }
+    }
+
+    pub fn secret_key(&self) -> &SecretKey {
+        &self.secret_key
+    }
+
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    /// The base58check-encoded address derived from this wallet's public
+    /// key (see `public_key_to_address`).
+    pub fn address(&self) -> String {
+        public_key_to_address(&self.public_key)
+    }
+}
+
+impl Default for Wallet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Derives a base58check address from a public key: a version byte
+/// followed by `RIPEMD160(SHA256(pubkey))`, with a 4-byte double-SHA256
+/// checksum appended before base58 encoding — the same structure Bitcoin's
+/// legacy P2PKH addresses use.
+pub fn public_key_to_address(public_key: &PublicKey) -> String {
+    let pubkey_hash = hash_public_key(public_key);
+
+    let mut payload = Vec::with_capacity(25);
+    payload.push(ADDRESS_VERSION);
+    payload.extend_from_slice(&pubkey_hash);
+
+    let checksum = dhash256(&payload);
+    payload.extend_from_slice(&checksum[..4]);
+
+    bs58::encode(payload).into_string()
+}
+
+fn hash_public_key(public_key: &PublicKey) -> [u8; 20] {
+    let sha256 = Sha256::digest(public_key.serialize());
+    Ripemd160::digest(sha256).into()
+}
+
+fn dhash256(data: &[u8]) -> [u8; 32] {
+    let first = Sha256::digest(data);
+    let second = Sha256::digest(first);
+    second.into()
+}
+
+/// Produces a compact ECDSA signature over a 32-byte digest.
+pub fn sign(secret_key: &SecretKey, digest: &[u8; 32]) -> [u8; 64] {
+    let secp = Secp256k1::new();
+    let message = Message::from_digest(*digest);
+    secp.sign_ecdsa(&message, secret_key).serialize_compact()
+}
+
+/// Checks a compact ECDSA signature over a 32-byte digest against a public key.
+pub fn verify(public_key: &PublicKey, digest: &[u8; 32], signature: &[u8; 64]) -> bool {
+    let secp = Secp256k1::new();
+    let message = Message::from_digest(*digest);
+    let signature = match Signature::from_compact(signature) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+    secp.verify_ecdsa(&message, &signature, public_key).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address_is_base58_and_deterministic() {
+        let wallet = Wallet::new();
+        let address = wallet.address();
+        assert_eq!(address, public_key_to_address(wallet.public_key()));
+        // bs58 alphabet excludes 0, O, I, l.
+        assert!(!address.contains('0'));
+        assert!(!address.contains('O'));
+        assert!(!address.contains('I'));
+        assert!(!address.contains('l'));
+    }
+
+    #[test]
+    fn test_different_keys_yield_different_addresses() {
+        let a = Wallet::new();
+        let b = Wallet::new();
+        assert_ne!(a.address(), b.address());
+    }
+
+    #[test]
+    fn test_sign_and_verify_round_trip() {
+        let wallet = Wallet::new();
+        let digest = [7u8; 32];
+        let signature = sign(wallet.secret_key(), &digest);
+        assert!(verify(wallet.public_key(), &digest, &signature));
+    }
+
+    #[test]
+    fn test_verify_rejects_wrong_key() {
+        let signer = Wallet::new();
+        let other = Wallet::new();
+        let digest = [7u8; 32];
+        let signature = sign(signer.secret_key(), &digest);
+        assert!(!verify(other.public_key(), &digest, &signature));
+    }
+
+    #[test]
+    fn test_verify_rejects_tampered_digest() {
+        let wallet = Wallet::new();
+        let digest = [7u8; 32];
+        let signature = sign(wallet.secret_key(), &digest);
+        assert!(!verify(wallet.public_key(), &[8u8; 32], &signature));
+    }
+}